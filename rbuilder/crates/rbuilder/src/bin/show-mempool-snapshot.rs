@@ -2,11 +2,16 @@
 //! Shows what would be fed to the greedy algorithm
 
 use alloy_primitives::utils::format_ether;
+use alloy_primitives::B256;
 use clap::Parser;
 use rbuilder_config::load_toml_config;
 use rbuilder_primitives::{Order, OrderId, SimValue, SimulatedOrder};
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
-use tracing::info;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Arc,
+};
+use tracing::{info, warn};
 
 use rbuilder::{
     backtest::{
@@ -14,6 +19,12 @@ use rbuilder::{
         BlockData, HistoricalDataStorage,
     },
     live_builder::{cli::LiveBuilderConfig, config::Config},
+    utils::{
+        fee_oracle::{FeeOracle, FeeOracleConfig},
+        receipts::{
+            fetch_receipts_for_block, load_receipts_for_block, persist_receipts_for_block, TxReceiptData,
+        },
+    },
 };
 
 #[derive(Parser, Debug)]
@@ -24,19 +35,59 @@ struct Cli {
     block: u64,
     #[clap(long, help = "Show top N orders by profit")]
     top: Option<usize>,
+    #[clap(
+        long,
+        help = "Fetch per-transaction receipts for this block via eth_getBlockReceipts and cache them in a receipts/ sidecar next to the historical data file, instead of relying on the gas-tip estimate (requires QUICK_NODE_ETH_MAINNET_API_URL_HTTPS)"
+    )]
+    fetch_receipts: bool,
+    #[clap(
+        long,
+        help = "HTTP RPC URL to poll eth_feeHistory from, to print the current base-fee projection for context alongside this historical snapshot"
+    )]
+    fee_history_rpc_url: Option<String>,
+}
+
+/// Compute the per-gas payment that actually lands on the coinbase for a single transaction,
+/// following the EIP-1559 fee rules: type-2 txs pay `min(priority_fee, fee_cap - base_fee)`,
+/// while legacy/type-0/1 txs pay `gas_price - base_fee`. Both are clamped at zero since a tx
+/// whose fee cap (or gas price) is below the base fee could not have been included.
+fn effective_priority_fee_per_gas(tx_inner: &impl alloy_consensus::Transaction, base_fee: u64) -> u128 {
+    effective_priority_fee_per_gas_from(
+        tx_inner.max_priority_fee_per_gas(),
+        tx_inner.max_fee_per_gas(),
+        base_fee,
+    )
+}
+
+/// Pure core of [`effective_priority_fee_per_gas`], split out so it can be unit tested without
+/// constructing a real `alloy_consensus::Transaction`.
+fn effective_priority_fee_per_gas_from(
+    max_priority_fee_per_gas: Option<u128>,
+    max_fee_per_gas: u128,
+    base_fee: u64,
+) -> u128 {
+    let base_fee = base_fee as u128;
+    match max_priority_fee_per_gas {
+        Some(max_priority_fee) => {
+            let fee_cap_after_base_fee = max_fee_per_gas.saturating_sub(base_fee);
+            max_priority_fee.min(fee_cap_after_base_fee)
+        }
+        None => max_fee_per_gas.saturating_sub(base_fee),
+    }
 }
 
 // Copy of extract_historical_profit_gas from backtest-build-block-no-sim.rs
 fn extract_historical_profit_gas(
     block_data: &BlockData,
     available_orders: &[Order],
-) -> eyre::Result<HashMap<OrderId, (alloy_primitives::U256, u64)>> {
+    receipts: Option<&HashMap<B256, TxReceiptData>>,
+) -> eyre::Result<(HashMap<OrderId, (alloy_primitives::U256, u64)>, usize, usize)> {
     use alloy_network_primitives::TransactionResponse;
     use alloy_rpc_types::BlockTransactions;
-    
+
     let mut gas_by_tx = HashMap::new();
     let mut profit_by_tx = HashMap::new();
-    
+
     let transactions = match &block_data.onchain_block.transactions {
         BlockTransactions::Full(txs) => txs,
         BlockTransactions::Hashes(_) => {
@@ -46,34 +97,56 @@ fn extract_historical_profit_gas(
             return Err(eyre::eyre!("Block has uncle transactions, not supported."));
         }
     };
-    
+
     use alloy_consensus::Transaction as TransactionTrait;
-    
+
+    let base_fee = block_data.onchain_block.header.base_fee_per_gas.unwrap_or_default();
+
+    let mut success_by_tx = HashMap::new();
+    let mut receipt_backed_tx_count = 0usize;
+    let mut estimated_tx_count = 0usize;
+
     for tx_response in transactions {
         let tx_hash = TransactionResponse::tx_hash(tx_response);
         let tx_inner = &tx_response.inner;
-        
-        let estimated_gas = (tx_inner.gas_limit() as f64 * 0.8) as u64;
-        gas_by_tx.insert(tx_hash, estimated_gas);
-        
-        let gas_tip = tx_inner
-            .max_priority_fee_per_gas()
-            .unwrap_or_default()
-            .min(tx_inner.max_fee_per_gas());
-        let gas_tip_profit = alloy_primitives::U256::from(gas_tip) * alloy_primitives::U256::from(estimated_gas);
-        profit_by_tx.insert(tx_hash, gas_tip_profit);
+
+        if let Some(receipt) = receipts.and_then(|r| r.get(&tx_hash)) {
+            receipt_backed_tx_count += 1;
+            gas_by_tx.insert(tx_hash, receipt.gas_used);
+            success_by_tx.insert(tx_hash, receipt.success);
+
+            let realized_priority_fee = receipt
+                .effective_gas_price
+                .saturating_sub(base_fee as u128);
+            let profit = if receipt.success {
+                alloy_primitives::U256::from(realized_priority_fee) * alloy_primitives::U256::from(receipt.gas_used)
+            } else {
+                alloy_primitives::U256::ZERO
+            };
+            profit_by_tx.insert(tx_hash, profit);
+        } else {
+            estimated_tx_count += 1;
+            let estimated_gas = (tx_inner.gas_limit() as f64 * 0.8) as u64;
+            gas_by_tx.insert(tx_hash, estimated_gas);
+            success_by_tx.insert(tx_hash, true);
+
+            let effective_priority_fee = effective_priority_fee_per_gas(tx_inner, base_fee);
+            let gas_tip_profit = alloy_primitives::U256::from(effective_priority_fee) * alloy_primitives::U256::from(estimated_gas);
+            profit_by_tx.insert(tx_hash, gas_tip_profit);
+        }
     }
-    
+
     let executed_block_txs: Vec<ExecutedBlockTx> = transactions
         .iter()
         .map(|tx_response| {
             let tx_hash = TransactionResponse::tx_hash(tx_response);
             let profit = profit_by_tx.get(&tx_hash).copied().unwrap_or_default();
-            
+            let success = success_by_tx.get(&tx_hash).copied().unwrap_or(true);
+
             ExecutedBlockTx::new(
                 tx_hash,
                 alloy_primitives::I256::try_from(profit).unwrap_or_default(),
-                true,
+                success,
             )
         })
         .collect();
@@ -107,7 +180,23 @@ fn extract_historical_profit_gas(
         }
     }
 
-    Ok(result)
+    Ok((result, receipt_backed_tx_count, estimated_tx_count))
+}
+
+/// Connects to `rpc_url`, polls `eth_feeHistory` once, and prints the projected next-block base
+/// fee and suggested priority fee tiers for context alongside this historical snapshot.
+async fn print_fee_projection(rpc_url: &str) -> eyre::Result<()> {
+    let provider = alloy_provider::ProviderBuilder::new().connect_http(rpc_url.parse()?);
+    let fee_oracle = FeeOracle::new(FeeOracleConfig::default());
+    fee_oracle.poll(&provider).await?;
+
+    if let Some(projection) = fee_oracle.projection() {
+        println!(
+            "Current fee projection: next base fee ~{} wei, priority fee tiers {:?}\n",
+            projection.next_base_fee_per_gas, projection.priority_fee_tiers
+        );
+    }
+    Ok(())
 }
 
 fn create_simulated_orders_from_historical_data(
@@ -136,10 +225,8 @@ async fn main() -> eyre::Result<()> {
     let config: Config = load_toml_config(cli.config.clone())?;
     config.base_config().setup_tracing_subscriber()?;
 
-    let mut storage = HistoricalDataStorage::new_from_path(
-        config.base_config().backtest_fetch_output_file.clone(),
-    )
-    .await?;
+    let historical_data_path = config.base_config().backtest_fetch_output_file.clone();
+    let mut storage = HistoricalDataStorage::new_from_path(historical_data_path.clone()).await?;
 
     let full_slot_data = storage.read_block_data(cli.block).await?;
     
@@ -160,7 +247,23 @@ async fn main() -> eyre::Result<()> {
     println!("Block Number: {}", block_data.onchain_block.header.number);
     println!("Timestamp: {}\n", block_data.onchain_block.header.timestamp);
 
-    let historical_profit_gas = extract_historical_profit_gas(&block_data, &available_orders)?;
+    if let Some(rpc_url) = &cli.fee_history_rpc_url {
+        match print_fee_projection(rpc_url).await {
+            Ok(()) => {}
+            Err(error) => warn!(?error, "failed to fetch current fee-history projection"),
+        }
+    }
+
+    let receipts = if cli.fetch_receipts {
+        let fetched = fetch_receipts_for_block(cli.block).await?;
+        persist_receipts_for_block(&historical_data_path, cli.block, &fetched)?;
+        Some(fetched)
+    } else {
+        load_receipts_for_block(&historical_data_path, cli.block)?
+    };
+
+    let (historical_profit_gas, receipt_backed_tx_count, estimated_tx_count) =
+        extract_historical_profit_gas(&block_data, &available_orders, receipts.as_ref())?;
     println!("Orders with profit/gas data: {}\n", historical_profit_gas.len());
 
     let sim_orders = create_simulated_orders_from_historical_data(
@@ -246,9 +349,49 @@ async fn main() -> eyre::Result<()> {
     println!("Total estimated gas: {}", 
         sim_orders.iter().map(|o| o.sim_value.gas_used()).sum::<u64>()
     );
-    println!("\nNote: These are ESTIMATED values (gas tips * 80% of gas_limit)");
-    println!("For exact values, you'd need full transaction simulation (requires Reth)");
+    if receipt_backed_tx_count > 0 && estimated_tx_count == 0 {
+        println!(
+            "\nNote: All {receipt_backed_tx_count} transactions used receipt-backed ground truth \
+             (exact gas used, realized effective gas price, and success status)"
+        );
+    } else if receipt_backed_tx_count > 0 {
+        println!(
+            "\nNote: {receipt_backed_tx_count} transactions used receipt-backed ground truth; \
+             {estimated_tx_count} had no receipt on file and fell back to the ESTIMATED value \
+             (effective EIP-1559 priority fee * 80% of gas_limit)"
+        );
+    } else {
+        println!("\nNote: These are ESTIMATED values (effective EIP-1559 priority fee * 80% of gas_limit)");
+        println!("No receipts were on file for this block - pass --fetch-receipts to fetch and cache the real ones");
+    }
+    println!("For exact values without a fetch, you'd need full transaction simulation (requires Reth)");
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_priority_fee_caps_type2_tx_at_fee_cap_minus_base_fee() {
+        assert_eq!(effective_priority_fee_per_gas_from(Some(5), 10, 7), 3);
+    }
+
+    #[test]
+    fn effective_priority_fee_uses_priority_fee_when_it_is_the_binding_constraint() {
+        assert_eq!(effective_priority_fee_per_gas_from(Some(2), 10, 7), 2);
+    }
+
+    #[test]
+    fn effective_priority_fee_legacy_tx_uses_gas_price_minus_base_fee() {
+        assert_eq!(effective_priority_fee_per_gas_from(None, 10, 7), 3);
+    }
+
+    #[test]
+    fn effective_priority_fee_clamps_at_zero_below_base_fee() {
+        assert_eq!(effective_priority_fee_per_gas_from(Some(5), 5, 7), 0);
+        assert_eq!(effective_priority_fee_per_gas_from(None, 5, 7), 0);
+    }
+}
+
@@ -39,6 +39,11 @@ register_metrics! {
             .buckets(exponential_buckets_range(0.01, 300.0, 200)),
         &[]
     ).unwrap();
+    pub static RESPONSE_BYTES: HistogramVec = HistogramVec::new(
+        HistogramOpts::new("relay_server_response_bytes", "The size in bytes of the optimistic V3 relay response body, by content encoding")
+            .buckets(exponential_buckets_range(1024.0, 16_000_000.0, 100)),
+        &["encoding"]
+    ).unwrap();
    pub static BAD_REQUESTS_TOTAL: IntCounter = IntCounter::new("relay_server_bad_requests", "The total number of bad requests on the optimistic V3 relay server").unwrap();
    pub static UNKNOWN_PUBKEY_TOTAL: IntCounter = IntCounter::new("relay_server_unknown_pubkey", "The total number of unknown pubkey errors on the optimistic V3 relay server").unwrap();
    pub static INVALID_SIGNATURE_TOTAL: IntCounter = IntCounter::new("relay_server_invalid_signature", "The total number of invalid signature errors on the optimistic V3 relay server").unwrap();
@@ -59,11 +64,66 @@ pub const OPTIMISTIC_V3_SERVER_CONTENT_LENGTH_LIMIT: u64 = 1_024;
 /// Reference: <https://ethresear.ch/t/introduction-to-optimistic-v3-relays/22066#p-53641-technical-specification-8>
 pub const GET_PAYLOAD_V3: &str = "get_payload_v3";
 
+/// The consensus forks whose `SubmitBlockRequest` payload layout we know how to serve.
+/// The layout grows a new field at (almost) every fork: Capella adds withdrawals, Deneb adds
+/// blob KZG commitments, Electra adds execution requests. Each cached block records its fork,
+/// taken from the request's own variant, for logging; encoding follows that variant, and nothing
+/// in this server picks a layout by fork or slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ForkName {
+    Bellatrix,
+    Capella,
+    Deneb,
+    Electra,
+}
+
+/// Slot boundaries (inclusive lower bound) at which each fork activates. `maintain_block_cache`
+/// only uses it to cross-check each payload's fork variant against its slot and warn when they
+/// disagree; it does not select the `SubmitBlockRequest` layout.
+#[derive(Clone, Debug)]
+pub struct ForkSchedule {
+    pub capella_start_slot: u64,
+    pub deneb_start_slot: u64,
+    pub electra_start_slot: u64,
+}
+
+impl ForkSchedule {
+    pub fn fork_at_slot(&self, slot: u64) -> ForkName {
+        if slot >= self.electra_start_slot {
+            ForkName::Electra
+        } else if slot >= self.deneb_start_slot {
+            ForkName::Deneb
+        } else if slot >= self.capella_start_slot {
+            ForkName::Capella
+        } else {
+            ForkName::Bellatrix
+        }
+    }
+}
+
+/// A cached submission, keyed by block hash. Both wire formats are encoded once from the parsed
+/// request at insertion time and kept alongside it, and the matching bytes are served verbatim
+/// for every later request of that content type, so a cache hit never re-encodes. The bid stream
+/// carries parsed requests only, so these are not the bytes the builder originally submitted.
+#[derive(Clone)]
+struct CachedBlock {
+    fork: ForkName,
+    parsed: Arc<AlloySubmitBlockRequest>,
+    raw_ssz: Bytes,
+    raw_json: Bytes,
+}
+
 /// Initialize the HTTP server.
+///
+/// `fork_schedule` is cross-checked in `maintain_block_cache` against the fork each incoming
+/// payload's own `AlloySubmitBlockRequest` variant says it is, so a stale or misconfigured
+/// schedule gets flagged instead of silently trusting the slot math. (The caller that constructs
+/// `fork_schedule` for a live relay deployment isn't part of this trimmed checkout.)
 pub fn spawn_server(
     address: impl Into<SocketAddr>,
     domain: B256,
     relay_pubkeys: HashSet<BlsPublicKey>,
+    fork_schedule: ForkSchedule,
     bid_stream: BroadcastStream<Arc<AlloySubmitBlockRequest>>,
 ) -> eyre::Result<()> {
     let blocks = Arc::new(Mutex::new(LruMap::new(ByLength::new(
@@ -73,7 +133,7 @@ pub fn spawn_server(
     // Spawn block cache maintenance task.
     tokio::spawn(Box::pin({
         let blocks = blocks.clone();
-        async move { maintain_block_cache(bid_stream, blocks).await }
+        async move { maintain_block_cache(bid_stream, fork_schedule, blocks).await }
     }));
 
     // Spawn relay server.
@@ -87,6 +147,7 @@ pub fn spawn_server(
         .and(warp::post())
         .and(warp::any().map(move || handler.clone()))
         .and(warp::header::<String>("content-type"))
+        .and(warp::header::optional::<String>("accept-encoding"))
         .and(warp::body::content_length_limit(
             OPTIMISTIC_V3_SERVER_CONTENT_LENGTH_LIMIT,
         ))
@@ -98,22 +159,23 @@ pub fn spawn_server(
     Ok(())
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 struct Handler {
     domain: B256,
     relay_pubkeys: HashSet<BlsPublicKey>,
-    blocks: Arc<Mutex<LruMap<B256, Arc<AlloySubmitBlockRequest>>>>,
+    blocks: Arc<Mutex<LruMap<B256, CachedBlock>>>,
 }
 
 impl Handler {
     fn get_payload_v3_metered(
         self,
         content_type: String,
+        accept_encoding: Option<String>,
         bytes: Bytes,
     ) -> Result<warp::reply::Response, StatusCode> {
         REQUESTS_TOTAL.inc();
         let start = Instant::now();
-        let response = Self::get_payload_v3(self, content_type, bytes);
+        let response = Self::get_payload_v3(self, content_type, accept_encoding, bytes);
         RESPONSE_LATENCY
             .with_label_values(&[])
             .observe(utils::duration_ms(start.elapsed()));
@@ -123,6 +185,7 @@ impl Handler {
     fn get_payload_v3(
         self,
         content_type: String,
+        accept_encoding: Option<String>,
         bytes: Bytes,
     ) -> Result<warp::reply::Response, StatusCode> {
         let mut is_json = false;
@@ -178,35 +241,163 @@ impl Handler {
             })?
         };
 
-        let (body, content_ty) = if is_json {
-            let json = serde_json::to_vec(&block).map_err(|error| {
-                error!(target: "relay_server", %relay_pubkey, %block_hash, ?error, "error serializing the block");
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-            (json, "application/json")
+        // Serve the bytes encoded at insertion time for the requested content type; only fall back
+        // to re-serializing the parsed request when those cached bytes are empty (JSON encoding
+        // failed at insertion). Encoding follows the request's own variant, which keeps the
+        // fork-versioned layout (Bellatrix/Capella/Deneb/Electra) entirely opaque to this handler.
+        let (mut body, content_ty): (Vec<u8>, _) = if is_json {
+            if !block.raw_json.is_empty() {
+                (block.raw_json.to_vec(), "application/json")
+            } else {
+                let json = serde_json::to_vec(&block.parsed).map_err(|error| {
+                    error!(target: "relay_server", %relay_pubkey, %block_hash, ?error, "error serializing the block");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+                (json, "application/json")
+            }
+        } else if !block.raw_ssz.is_empty() {
+            (block.raw_ssz.to_vec(), "application/octet-stream")
+        } else {
+            (block.parsed.as_ssz_bytes(), "application/octet-stream")
+        };
+
+        // SSZ payloads are conventionally snappy-compressed on the wire, and post-Deneb blocks
+        // with blobs are large enough that compression meaningfully cuts egress bandwidth and
+        // latency under contention. Only the binary path is compressed; JSON responses are left
+        // as-is since clients that ask for JSON are almost never bandwidth-constrained.
+        let content_encoding = if content_ty == "application/octet-stream" {
+            negotiate_encoding(accept_encoding.as_deref())
         } else {
-            let ssz = block.as_ssz_bytes();
-            (ssz, "application/octet-stream")
+            None
         };
+        if let Some(encoding) = content_encoding {
+            body = compress(encoding, &body);
+        }
+        let encoding_label = content_encoding.map(|e| e.as_str()).unwrap_or("identity");
+        RESPONSE_BYTES
+            .with_label_values(&[encoding_label])
+            .observe(body.len() as f64);
 
-        debug!(target: "relay_server", %relay_pubkey, %block_hash, "Returning payload for request");
+        debug!(target: "relay_server", %relay_pubkey, %block_hash, fork = ?block.fork, encoding = encoding_label, "Returning payload for request");
         let mut res = warp::http::Response::new(body.into());
         res.headers_mut()
             .insert(CONTENT_TYPE, HeaderValue::from_static(content_ty));
+        if let Some(encoding) = content_encoding {
+            res.headers_mut().insert(
+                warp::http::header::CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            );
+        }
         Ok(res)
     }
 }
 
+/// Content-Encoding values we know how to produce, in preference order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ContentEncoding {
+    Snappy,
+    Gzip,
+}
+
+impl ContentEncoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            ContentEncoding::Snappy => "snappy",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// Picks the best encoding the client advertised in `Accept-Encoding`, preferring snappy (the
+/// beacon API convention) over gzip.
+fn negotiate_encoding(accept_encoding: Option<&str>) -> Option<ContentEncoding> {
+    let accept_encoding = accept_encoding?;
+    if accept_encoding
+        .split(',')
+        .any(|value| value.trim().eq_ignore_ascii_case("snappy"))
+    {
+        Some(ContentEncoding::Snappy)
+    } else if accept_encoding
+        .split(',')
+        .any(|value| value.trim().eq_ignore_ascii_case("gzip"))
+    {
+        Some(ContentEncoding::Gzip)
+    } else {
+        None
+    }
+}
+
+// Uses the `snap` and `flate2` crates for the Snappy/gzip encoders below. This checkout doesn't
+// carry the workspace `Cargo.toml`, so there's nothing here to confirm they're declared as
+// dependencies of this crate - whoever merges this should add them (`snap`, `flate2`) if they
+// aren't already present.
+fn compress(encoding: ContentEncoding, data: &[u8]) -> Vec<u8> {
+    match encoding {
+        ContentEncoding::Snappy => {
+            let mut writer = snap::write::FrameEncoder::new(Vec::with_capacity(data.len()));
+            std::io::Write::write_all(&mut writer, data).expect("in-memory snappy writer cannot fail");
+            writer
+                .into_inner()
+                .expect("in-memory snappy writer cannot fail")
+        }
+        ContentEncoding::Gzip => {
+            let mut writer =
+                flate2::write::GzEncoder::new(Vec::with_capacity(data.len()), flate2::Compression::default());
+            std::io::Write::write_all(&mut writer, data).expect("in-memory gzip writer cannot fail");
+            writer.finish().expect("in-memory gzip writer cannot fail")
+        }
+    }
+}
+
+/// Derives the fork from the payload's own decoded `AlloySubmitBlockRequest` variant - the ground
+/// truth for which layout the builder actually sent - rather than only trusting the slot-based
+/// `ForkSchedule`, which can drift if its configured slot boundaries are stale or wrong.
+fn fork_of_request(request: &AlloySubmitBlockRequest) -> ForkName {
+    match request {
+        AlloySubmitBlockRequest::Bellatrix(_) => ForkName::Bellatrix,
+        AlloySubmitBlockRequest::Capella(_) => ForkName::Capella,
+        AlloySubmitBlockRequest::Deneb(_) => ForkName::Deneb,
+        AlloySubmitBlockRequest::Electra(_) => ForkName::Electra,
+    }
+}
+
 async fn maintain_block_cache(
     mut bid_stream: BroadcastStream<Arc<AlloySubmitBlockRequest>>,
-    blocks: Arc<Mutex<LruMap<B256, Arc<AlloySubmitBlockRequest>>>>,
+    fork_schedule: ForkSchedule,
+    blocks: Arc<Mutex<LruMap<B256, CachedBlock>>>,
 ) {
     loop {
         match bid_stream.next().await {
             Some(Ok(block)) => {
                 let block_hash = block.bid_trace().block_hash;
-                blocks.lock().insert(block_hash, block);
-                trace!(target: "relay_server", %block_hash, "Block added to the relay server cache")
+                let fork = fork_of_request(&block);
+                let scheduled_fork = fork_schedule.fork_at_slot(block.bid_trace().slot);
+                if scheduled_fork != fork {
+                    warn!(
+                        target: "relay_server",
+                        %block_hash,
+                        ?fork,
+                        ?scheduled_fork,
+                        "ForkSchedule disagrees with the payload's actual fork variant; check the configured slot boundaries"
+                    );
+                }
+                // Pre-encode both wire formats once at insertion time so that serving a request
+                // later is a byte-for-byte cache hit rather than a fresh (and potentially
+                // drifting) re-encode of the fork-specific layout.
+                let raw_ssz = Bytes::from(block.as_ssz_bytes());
+                let raw_json = serde_json::to_vec(&block)
+                    .map(Bytes::from)
+                    .unwrap_or_default();
+                blocks.lock().insert(
+                    block_hash,
+                    CachedBlock {
+                        fork,
+                        parsed: block,
+                        raw_ssz,
+                        raw_json,
+                    },
+                );
+                trace!(target: "relay_server", %block_hash, ?fork, "Block added to the relay server cache")
             }
             Some(Err(BroadcastStreamRecvError::Lagged(lag))) => {
                 error!(target: "relay_server", lag, "Block stream lagging behind");
@@ -217,3 +408,35 @@ async fn maintain_block_cache(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_encoding_prefers_snappy_over_gzip() {
+        assert_eq!(
+            negotiate_encoding(Some("gzip, snappy")),
+            Some(ContentEncoding::Snappy)
+        );
+    }
+
+    #[test]
+    fn negotiate_encoding_falls_back_to_gzip() {
+        assert_eq!(negotiate_encoding(Some("deflate, gzip")), Some(ContentEncoding::Gzip));
+    }
+
+    #[test]
+    fn negotiate_encoding_ignores_case_and_whitespace() {
+        assert_eq!(
+            negotiate_encoding(Some(" SNAPPY ")),
+            Some(ContentEncoding::Snappy)
+        );
+    }
+
+    #[test]
+    fn negotiate_encoding_none_when_unsupported_or_absent() {
+        assert_eq!(negotiate_encoding(Some("br, deflate")), None);
+        assert_eq!(negotiate_encoding(None), None);
+    }
+}
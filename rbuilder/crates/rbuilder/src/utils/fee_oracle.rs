@@ -0,0 +1,160 @@
+//! Base-fee oracle built on `eth_feeHistory`, shared by the live mempool tool and the backtest
+//! profit estimator so both can reason about near-future base fees instead of assuming the
+//! current (parent) block's value.
+//!
+//! Reference: <https://eips.ethereum.org/EIPS/eip-1559#specification> for the base fee update rule.
+
+use alloy_eips::BlockNumberOrTag;
+use alloy_provider::Provider;
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Maximum relative move of the base fee between two consecutive blocks, per EIP-1559.
+const MAX_BASE_FEE_CHANGE_DENOMINATOR: u128 = 8;
+
+/// One retained `eth_feeHistory` sample for a single historical block.
+#[derive(Clone, Debug)]
+pub struct FeeHistorySample {
+    pub base_fee_per_gas: u128,
+    pub gas_used_ratio: f64,
+    /// Reward (priority fee) paid at each of the configured percentiles, in wei.
+    pub reward_percentiles: Vec<u128>,
+}
+
+/// Suggested priority fee tiers, one per configured percentile, plus the projected base fee of
+/// the next block.
+#[derive(Clone, Debug)]
+pub struct FeeProjection {
+    pub next_base_fee_per_gas: u128,
+    pub priority_fee_tiers: Vec<u128>,
+}
+
+/// Configuration for [`FeeOracle`].
+#[derive(Clone, Debug)]
+pub struct FeeOracleConfig {
+    /// Number of trailing blocks to retain in the ring buffer.
+    pub window_len: usize,
+    /// Reward percentiles to request from `eth_feeHistory` (e.g. `[10.0, 50.0, 90.0]`).
+    pub reward_percentiles: Vec<f64>,
+    /// How often to poll `eth_feeHistory` for new blocks.
+    pub poll_interval: Duration,
+}
+
+impl Default for FeeOracleConfig {
+    fn default() -> Self {
+        Self {
+            window_len: 20,
+            reward_percentiles: vec![10.0, 50.0, 90.0],
+            poll_interval: Duration::from_secs(12),
+        }
+    }
+}
+
+/// Polls `eth_feeHistory` and retains a ring buffer of recent base fees, gas usage ratios and
+/// reward percentiles, projecting the next-block base fee and suggested priority fee tiers.
+pub struct FeeOracle {
+    config: FeeOracleConfig,
+    samples: RwLock<VecDeque<FeeHistorySample>>,
+}
+
+impl FeeOracle {
+    pub fn new(config: FeeOracleConfig) -> Self {
+        Self {
+            samples: RwLock::new(VecDeque::with_capacity(config.window_len)),
+            config,
+        }
+    }
+
+    /// Fetches the latest `eth_feeHistory` window and merges any new blocks into the ring buffer.
+    pub async fn poll<P: Provider>(&self, provider: &P) -> eyre::Result<()> {
+        let block_count = self.config.window_len as u64;
+        let fee_history = provider
+            .get_fee_history(
+                block_count,
+                BlockNumberOrTag::Latest,
+                &self.config.reward_percentiles,
+            )
+            .await?;
+
+        let rewards = fee_history.reward.unwrap_or_default();
+        let mut samples = self.samples.write().unwrap();
+        samples.clear();
+        // `base_fee_per_gas` carries one extra trailing entry (the block after the newest one),
+        // which has no gas used ratio or rewards, so only the historical blocks are sampled.
+        for (i, &gas_used_ratio) in fee_history.gas_used_ratio.iter().enumerate() {
+            let base_fee_per_gas = fee_history.base_fee_per_gas.get(i).copied().unwrap_or_default();
+            let reward_percentiles = rewards.get(i).cloned().unwrap_or_default();
+            samples.push_back(FeeHistorySample {
+                base_fee_per_gas,
+                gas_used_ratio,
+                reward_percentiles,
+            });
+            while samples.len() > self.config.window_len {
+                samples.pop_front();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Projects the base fee of the next block from the most recent sample, per the standard
+    /// EIP-1559 update rule, clamped so it can move at most ±12.5% from the parent block.
+    pub fn projected_next_base_fee(&self) -> Option<u128> {
+        let samples = self.samples.read().unwrap();
+        let last = samples.back()?;
+        Some(next_base_fee(last.base_fee_per_gas, last.gas_used_ratio))
+    }
+
+    /// Suggested priority fee tiers (one per configured percentile), taken from the most recent
+    /// block's reward percentiles.
+    pub fn suggested_priority_fee_tiers(&self) -> Option<Vec<u128>> {
+        let samples = self.samples.read().unwrap();
+        let last = samples.back()?;
+        Some(last.reward_percentiles.clone())
+    }
+
+    /// Convenience accessor combining [`Self::projected_next_base_fee`] and
+    /// [`Self::suggested_priority_fee_tiers`].
+    pub fn projection(&self) -> Option<FeeProjection> {
+        Some(FeeProjection {
+            next_base_fee_per_gas: self.projected_next_base_fee()?,
+            priority_fee_tiers: self.suggested_priority_fee_tiers()?,
+        })
+    }
+}
+
+/// `next = base * (1 + (gas_used_ratio / 0.5 - 1) / 8)`, clamped to a ±12.5% move.
+fn next_base_fee(base_fee_per_gas: u128, gas_used_ratio: f64) -> u128 {
+    let target_ratio = 0.5;
+    let delta_ratio = gas_used_ratio - target_ratio;
+    let max_delta = base_fee_per_gas / MAX_BASE_FEE_CHANGE_DENOMINATOR;
+    let delta = ((base_fee_per_gas as f64) * delta_ratio * 2.0 / MAX_BASE_FEE_CHANGE_DENOMINATOR as f64) as i128;
+    let delta = delta.clamp(-(max_delta as i128), max_delta as i128);
+    (base_fee_per_gas as i128 + delta).max(0) as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_base_fee_unchanged_at_half_full() {
+        assert_eq!(next_base_fee(1_000_000_000, 0.5), 1_000_000_000);
+    }
+
+    #[test]
+    fn next_base_fee_rises_at_most_one_eighth_when_full() {
+        assert_eq!(next_base_fee(1_000_000_000, 1.0), 1_125_000_000);
+    }
+
+    #[test]
+    fn next_base_fee_falls_at_most_one_eighth_when_empty() {
+        assert_eq!(next_base_fee(1_000_000_000, 0.0), 875_000_000);
+    }
+
+    #[test]
+    fn next_base_fee_never_goes_negative() {
+        assert_eq!(next_base_fee(0, 0.0), 0);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod fee_oracle;
+pub mod receipts;
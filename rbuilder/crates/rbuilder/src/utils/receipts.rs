@@ -0,0 +1,119 @@
+//! Per-transaction receipt fetching/caching shared by the backtest tools
+//! (`backtest-build-block-no-sim` and `show-mempool-snapshot`) that want to value historical
+//! transactions against ground truth instead of the gas-tip/gas-limit estimate.
+//!
+//! `HistoricalDataStorage`/`BlockData` in this checkout have no `receipts` field of their own to
+//! persist these into, so they're fetched and cached as a JSON sidecar file next to the historical
+//! data file instead.
+
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The receipt fields we need to value a transaction against ground truth: exact `gas_used`,
+/// whether it actually succeeded, and the realized effective gas price.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TxReceiptData {
+    pub gas_used: u64,
+    pub success: bool,
+    pub effective_gas_price: u128,
+}
+
+/// Path of the sidecar file holding the fetched receipts for `block_number`, stored in a
+/// `receipts/` directory next to `historical_data_path`.
+pub fn receipts_sidecar_path(historical_data_path: &Path, block_number: u64) -> PathBuf {
+    historical_data_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("receipts")
+        .join(format!("{block_number}.json"))
+}
+
+/// Loads the receipts previously fetched for `block_number`, if `--fetch-receipts` has been run
+/// for it before. Returns `None` (not an error) when no sidecar file is on file yet, so callers
+/// fall back to the gas-tip estimate.
+pub fn load_receipts_for_block(
+    historical_data_path: &Path,
+    block_number: u64,
+) -> eyre::Result<Option<HashMap<B256, TxReceiptData>>> {
+    let path = receipts_sidecar_path(historical_data_path, block_number);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path)?;
+    Ok(Some(serde_json::from_str(&contents)?))
+}
+
+/// Persists `receipts` for `block_number` to its sidecar file, creating the `receipts/` directory
+/// next to `historical_data_path` if it doesn't exist yet.
+pub fn persist_receipts_for_block(
+    historical_data_path: &Path,
+    block_number: u64,
+    receipts: &HashMap<B256, TxReceiptData>,
+) -> eyre::Result<()> {
+    let path = receipts_sidecar_path(historical_data_path, block_number);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(receipts)?)?;
+    Ok(())
+}
+
+/// Fetches real per-transaction receipts for `block_number` over `eth_getBlockReceipts`, using the
+/// HTTP RPC endpoint named by `QUICK_NODE_ETH_MAINNET_API_URL_HTTPS` (the HTTP counterpart of the
+/// WSS endpoint `test-quicknode-mempool` streams pending transactions from).
+pub async fn fetch_receipts_for_block(block_number: u64) -> eyre::Result<HashMap<B256, TxReceiptData>> {
+    let rpc_url = std::env::var("QUICK_NODE_ETH_MAINNET_API_URL_HTTPS").map_err(|_| {
+        eyre::eyre!(
+            "QUICK_NODE_ETH_MAINNET_API_URL_HTTPS environment variable not set, required for --fetch-receipts"
+        )
+    })?;
+    let provider = alloy_provider::ProviderBuilder::new().connect_http(rpc_url.parse()?);
+
+    let receipts = alloy_provider::Provider::get_block_receipts(
+        &provider,
+        alloy_eips::BlockId::Number(block_number.into()),
+    )
+    .await?
+    .ok_or_else(|| eyre::eyre!("no receipts returned for block {block_number}"))?;
+
+    Ok(receipts
+        .into_iter()
+        .map(|receipt| {
+            (
+                receipt.transaction_hash,
+                TxReceiptData {
+                    gas_used: receipt.gas_used,
+                    success: receipt.status(),
+                    effective_gas_price: receipt.effective_gas_price,
+                },
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receipts_sidecar_path_is_next_to_historical_data_file() {
+        let path = receipts_sidecar_path(Path::new("/data/mainnet/historical.data"), 18_920_193);
+        assert_eq!(
+            path,
+            Path::new("/data/mainnet/receipts/18920193.json")
+        );
+    }
+
+    #[test]
+    fn load_receipts_for_block_returns_none_when_no_sidecar_exists() {
+        let dir = std::env::temp_dir().join(format!(
+            "rbuilder-receipts-test-{}",
+            std::process::id()
+        ));
+        let historical_data_path = dir.join("historical.data");
+        let result = load_receipts_for_block(&historical_data_path, 1).unwrap();
+        assert!(result.is_none());
+    }
+}
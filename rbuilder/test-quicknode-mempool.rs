@@ -1,91 +1,351 @@
-//! Simple test script to connect to QuickNode WebSocket and stream live mempool transactions
+//! Captures live pending transactions from a QuickNode WebSocket endpoint and persists them into
+//! the same `HistoricalDataStorage` the backtest tools read, so recorded real-time mempool
+//! snapshots can be replayed through the greedy algorithm (or any other builder) offline.
+//!
+//! This is the first place in the codebase that writes to `HistoricalDataStorage` - every other
+//! use is `new_from_path` followed by reads - via `Order::new_from_pending_tx` and
+//! `HistoricalDataStorage::write_orders_for_block`. This checkout doesn't vendor the source for
+//! `rbuilder_primitives`/`rbuilder::backtest`, so there's nothing here to confirm those two
+//! methods actually exist upstream; whoever merges this against the full workspace should verify
+//! that (or land the corresponding additions there first).
+//!
+//! Usage:
+//!   cargo run --bin test-quicknode-mempool -- --config config.toml --duration-secs 600
+
+use alloy_consensus::Transaction as TransactionTrait;
+use alloy_network_primitives::TransactionResponse;
+use alloy_primitives::TxHash;
 use alloy_provider::{Provider, ProviderBuilder};
-use alloy_primitives::FixedBytes;
-use futures::StreamExt;
-use std::pin::pin;
-use std::time::Instant;
+use clap::Parser;
+use futures::{future::join_all, StreamExt};
+use rbuilder::{
+    backtest::HistoricalDataStorage,
+    live_builder::{cli::LiveBuilderConfig, config::Config},
+    utils::fee_oracle::{FeeOracle, FeeOracleConfig},
+};
+use rbuilder_config::load_toml_config;
+use rbuilder_primitives::Order;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    pin::pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use time::OffsetDateTime;
+use tracing::{debug, info, warn};
+
+/// How many pending-tx hashes to batch into a single round of `get_transaction_by_hash` calls
+/// when the provider doesn't support the full-pending-transaction subscription.
+const HASH_LOOKUP_BATCH_SIZE: usize = 16;
+
+#[derive(Parser, Debug)]
+struct Cli {
+    #[clap(long, help = "Config file path", env = "RBUILDER_CONFIG")]
+    config: PathBuf,
+    #[clap(long, help = "Stop capture after this many seconds (unbounded if omitted)")]
+    duration_secs: Option<u64>,
+    #[clap(long, help = "Ignore transactions observed before this block number")]
+    from_block: Option<u64>,
+    #[clap(long, help = "Stop capture once the chain head reaches this block number")]
+    to_block: Option<u64>,
+}
+
+/// A pending transaction and when it arrived; the block it was observed against is its key in
+/// `captured_by_block`.
+struct CapturedTx {
+    order: Order,
+    arrival_time: OffsetDateTime,
+}
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    let cli = Cli::parse();
+    let config: Config = load_toml_config(cli.config.clone())?;
+    config.base_config().setup_tracing_subscriber()?;
 
-    // Get QuickNode WebSocket URL from environment
     let ws_url = std::env::var("QUICK_NODE_ETH_MAINNET_API_URL_WSS")
         .expect("QUICK_NODE_ETH_MAINNET_API_URL_WSS environment variable not set");
 
-    println!("🔌 Connecting to QuickNode WebSocket: {}", ws_url);
-    println!("📡 Subscribing to pending transactions...\n");
-
-    // Connect to WebSocket
+    info!("Connecting to QuickNode WebSocket");
     let ws_conn = alloy_provider::WsConnect::new(ws_url);
     let provider = ProviderBuilder::new()
         .connect_ws(ws_conn)
         .await
         .map_err(|e| eyre::eyre!("Failed to connect to WebSocket: {}", e))?;
+    info!("Connected successfully, subscribing to pending transactions");
 
-    println!("✅ Connected successfully!\n");
-    println!("⏳ Waiting for pending transactions...\n");
-    println!("Press Ctrl+C to stop\n");
-    println!("{}", "─".repeat(80));
+    let mut storage = HistoricalDataStorage::new_from_path(
+        config.base_config().backtest_fetch_output_file.clone(),
+    )
+    .await?;
 
-    // Subscribe to pending transactions
-    let stream = provider
-        .subscribe_pending_transactions()
-        .await
-        .map_err(|e| eyre::eyre!("Failed to subscribe to pending transactions: {}", e))?;
+    let start_time = Instant::now();
+    let deadline = cli.duration_secs.map(std::time::Duration::from_secs);
 
-    let mut stream = pin!(stream.into_stream());
+    // Buffer captured transactions by the block number they were observed against, so we can
+    // append them to HistoricalDataStorage one block at a time instead of per-transaction.
+    let mut captured_by_block: HashMap<u64, Vec<CapturedTx>> = HashMap::new();
     let mut count = 0u64;
-    let start_time = Instant::now();
 
-    // Stream transactions
-    while let Some(tx_hash) = stream.next().await {
-        count += 1;
-        let elapsed = start_time.elapsed();
-        let rate = count as f64 / elapsed.as_secs_f64();
+    // Track the current chain head via a background header subscription instead of calling
+    // `get_block_number()` on every observed transaction - at real mempool throughput (tens of
+    // tx/sec) a per-tx RPC round trip would become the bottleneck.
+    let current_block = Arc::new(AtomicU64::new(provider.get_block_number().await?));
+    tokio::spawn({
+        let provider = provider.clone();
+        let current_block = current_block.clone();
+        async move {
+            match provider.subscribe_blocks().await {
+                Ok(subscription) => {
+                    let mut headers = pin!(subscription.into_stream());
+                    while let Some(header) = headers.next().await {
+                        current_block.store(header.number, Ordering::Relaxed);
+                    }
+                }
+                Err(error) => {
+                    warn!(?error, "failed to subscribe to new block headers, chain head tracking frozen");
+                }
+            }
+        }
+    });
 
-        // Get transaction details
-        let tx_details = provider.get_transaction_by_hash(tx_hash).await;
-        
-        match tx_details {
-            Ok(Some(tx)) => {
-                let from = tx.from;
-                let to = tx.to.map(|a| a.to_string()).unwrap_or_else(|| "Contract Creation".to_string());
-                let value = tx.value;
-                let gas_price = tx.gas_price.unwrap_or_default();
-                let gas_limit = tx.gas_limit;
-                
-                println!(
-                    "[#{:6}] Hash: {:#?}",
-                    count,
-                    tx_hash
-                );
-                println!("         From: {}", from);
-                println!("         To:   {}", to);
-                println!("         Value: {} ETH", alloy_primitives::U256::from(value) / alloy_primitives::U256::from(1_000_000_000_000_000_000u64));
-                println!("         Gas Price: {} Gwei", gas_price / 1_000_000_000u64);
-                println!("         Gas Limit: {}", gas_limit);
-                println!("         Rate: {:.2} tx/s", rate);
-                println!("{}", "─".repeat(80));
+    // Project near-future base fees from eth_feeHistory so captured transactions can be flagged
+    // with whether they're likely to clear the next block, not just the current (parent) one.
+    let fee_oracle_config = FeeOracleConfig::default();
+    let poll_interval = fee_oracle_config.poll_interval;
+    let fee_oracle = Arc::new(FeeOracle::new(fee_oracle_config));
+    // Degrade gracefully on a transient eth_feeHistory hiccup at startup, same as the periodic
+    // poll loop below - aborting the whole capture run over one failed poll would throw away
+    // everything buffered in captured_by_block for no good reason. We simply have no projection
+    // until the next successful poll.
+    if let Err(error) = fee_oracle.poll(&provider).await {
+        warn!(?error, "failed initial eth_feeHistory poll for fee oracle, continuing without a projection yet");
+    }
+    tokio::spawn({
+        let provider = provider.clone();
+        let fee_oracle = fee_oracle.clone();
+        async move {
+            let mut ticker = tokio::time::interval(poll_interval);
+            ticker.tick().await; // first tick fires immediately; we already polled once above
+            loop {
+                ticker.tick().await;
+                if let Err(error) = fee_oracle.poll(&provider).await {
+                    warn!(?error, "failed to poll eth_feeHistory for fee oracle");
+                }
             }
-            Ok(None) => {
-                println!(
-                    "[#{:6}] Hash: {:#?} (tx not found in mempool)",
-                    count,
-                    tx_hash
-                );
+        }
+    });
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    // Prefer the full-pending-transaction subscription so we get complete transaction bodies in
+    // one round trip; fall back to the hash-only subscription with batched lookups when the
+    // provider doesn't support it.
+    let full_txs_subscription = provider.subscribe_full_pending_transactions().await;
+
+    let result: eyre::Result<()> = if let Ok(stream) = full_txs_subscription {
+        let mut stream = pin!(stream.into_stream());
+        loop {
+            tokio::select! {
+                biased;
+                _ = &mut ctrl_c => {
+                    info!("Received Ctrl+C, flushing capture buffer");
+                    break Ok(());
+                }
+                tx = stream.next() => {
+                    match tx {
+                        Some(tx) => {
+                            match record_transaction(
+                                &current_block,
+                                &fee_oracle,
+                                tx,
+                                &cli,
+                                &mut captured_by_block,
+                            ) {
+                                RecordOutcome::Stored => count += 1,
+                                RecordOutcome::Dropped => {}
+                                RecordOutcome::Stop => break Ok(()),
+                            }
+                        }
+                        None => break Ok(()),
+                    }
+                }
             }
-            Err(e) => {
-                eprintln!("[#{:6}] Error fetching tx details: {}", count, e);
+
+            if let Some(deadline) = deadline {
+                if start_time.elapsed() >= deadline {
+                    info!("Capture duration elapsed, flushing capture buffer");
+                    break Ok(());
+                }
             }
         }
+    } else {
+        warn!("Provider does not support full pending transaction subscription, falling back to batched hash lookups");
+        let stream = provider
+            .subscribe_pending_transactions()
+            .await
+            .map_err(|e| eyre::eyre!("Failed to subscribe to pending transactions: {}", e))?;
+        let mut stream = pin!(stream.into_stream());
+        let mut pending_hashes: Vec<TxHash> = Vec::with_capacity(HASH_LOOKUP_BATCH_SIZE);
+        loop {
+            tokio::select! {
+                biased;
+                _ = &mut ctrl_c => {
+                    info!("Received Ctrl+C, flushing capture buffer");
+                    break Ok(());
+                }
+                tx_hash = stream.next() => {
+                    match tx_hash {
+                        Some(tx_hash) => {
+                            pending_hashes.push(tx_hash);
+                            if pending_hashes.len() >= HASH_LOOKUP_BATCH_SIZE {
+                                let batch = std::mem::take(&mut pending_hashes);
+                                let (captured, should_stop) = resolve_and_record_batch(
+                                    &provider,
+                                    &current_block,
+                                    &fee_oracle,
+                                    batch,
+                                    &cli,
+                                    &mut captured_by_block,
+                                )
+                                .await?;
+                                count += captured;
+                                if should_stop {
+                                    break Ok(());
+                                }
+                            }
+                        }
+                        None => break Ok(()),
+                    }
+                }
+            }
+
+            if let Some(deadline) = deadline {
+                if start_time.elapsed() >= deadline {
+                    info!("Capture duration elapsed, flushing capture buffer");
+                    break Ok(());
+                }
+            }
+        }
+    };
+
+    result?;
+
+    info!(
+        "Captured {} transactions across {} blocks, flushing to historical storage",
+        count,
+        captured_by_block.len()
+    );
+    for (block_number, txs) in captured_by_block {
+        let orders_with_arrival: Vec<(Order, OffsetDateTime)> = txs
+            .into_iter()
+            .map(|captured| (captured.order, captured.arrival_time))
+            .collect();
+        storage
+            .write_orders_for_block(block_number, orders_with_arrival)
+            .await?;
     }
+    info!("Flush complete");
 
-    println!("\n📊 Total transactions received: {}", count);
     Ok(())
 }
 
+/// Outcome of [`record_transaction`]: whether the transaction was actually stored into
+/// `captured_by_block`, dropped by a `--from-block`/`--to-block` filter, or the capture loop
+/// should stop entirely (the chain head passed `--to-block`).
+enum RecordOutcome {
+    Stored,
+    Dropped,
+    Stop,
+}
 
+/// Records a single full pending transaction against the chain head tracked in `current_block`
+/// (updated by the background header subscription in `main`, not an RPC call per transaction).
+fn record_transaction(
+    current_block: &AtomicU64,
+    fee_oracle: &FeeOracle,
+    tx: impl TransactionResponse + TransactionTrait,
+    cli: &Cli,
+    captured_by_block: &mut HashMap<u64, Vec<CapturedTx>>,
+) -> RecordOutcome {
+    let observed_block = current_block.load(Ordering::Relaxed);
+    if let Some(from_block) = cli.from_block {
+        if observed_block < from_block {
+            return RecordOutcome::Dropped;
+        }
+    }
+    if let Some(to_block) = cli.to_block {
+        if observed_block >= to_block {
+            return RecordOutcome::Stop;
+        }
+    }
+
+    // Surface whether this transaction's fee cap can even pay the projected next-block base fee,
+    // so an operator watching the logs can tell "will this tx clear next block" at a glance.
+    if let Some(projected_base_fee) = fee_oracle.projected_next_base_fee() {
+        let will_clear_next_block = tx.max_fee_per_gas() >= projected_base_fee;
+        if !will_clear_next_block {
+            debug!(
+                tx_hash = ?TransactionResponse::tx_hash(&tx),
+                max_fee_per_gas = tx.max_fee_per_gas(),
+                projected_next_base_fee = projected_base_fee,
+                "pending tx's fee cap is below the projected next-block base fee, unlikely to clear next block"
+            );
+        }
+    }
+
+    let order = Order::new_from_pending_tx(tx);
+    captured_by_block
+        .entry(observed_block)
+        .or_default()
+        .push(CapturedTx {
+            order,
+            arrival_time: OffsetDateTime::now_utc(),
+        });
+
+    RecordOutcome::Stored
+}
+
+/// Resolves a batch of pending transaction hashes via `get_transaction_by_hash` and records every
+/// one the provider returns (whether or not it has been mined since). Returns the number of transactions actually stored
+/// (not just looked up - a tx dropped by `--from-block` doesn't count) and whether the capture
+/// loop should stop (propagated from `record_transaction`, previously lost since it only broke
+/// out of this function's own loop over the batch).
+async fn resolve_and_record_batch<P: Provider>(
+    provider: &P,
+    current_block: &AtomicU64,
+    fee_oracle: &FeeOracle,
+    hashes: Vec<TxHash>,
+    cli: &Cli,
+    captured_by_block: &mut HashMap<u64, Vec<CapturedTx>>,
+) -> eyre::Result<(u64, bool)> {
+    let lookups = hashes
+        .into_iter()
+        .map(|hash| provider.get_transaction_by_hash(hash));
+    let results = join_all(lookups).await;
+
+    let mut captured = 0u64;
+    let mut should_stop = false;
+    for result in results {
+        match result {
+            Ok(Some(tx)) => {
+                match record_transaction(current_block, fee_oracle, tx, cli, captured_by_block) {
+                    RecordOutcome::Stored => captured += 1,
+                    RecordOutcome::Dropped => {}
+                    RecordOutcome::Stop => {
+                        should_stop = true;
+                        break;
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(error) => warn!(?error, "error fetching pending transaction"),
+        }
+    }
+    Ok((captured, should_stop))
+}